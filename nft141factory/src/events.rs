@@ -0,0 +1,80 @@
+use near_sdk::serde::Serialize;
+use near_sdk::{serde_json, AccountId};
+
+/// NEP-297 `standard` tag carried by every log the factory emits.
+pub const NFT141_STANDARD: &str = "nft141";
+/// Semantic version of the event schema; bump on a breaking data change.
+pub const NFT141_VERSION: &str = "1.0.0";
+
+/// A single NEP-297 compliant event line. Serializes to
+/// `{"standard":"nft141","version":"1.0.0","event":<name>,"data":[...]}` and is
+/// logged behind the mandated `EVENT_JSON:` prefix via `Display`/`emit`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: &'static str,
+    pub version: &'static str,
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+/// Machine-readable activities reported by the factory. Append new variants to
+/// stay forward compatible with existing indexers.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EventLogVariant {
+    PairCreated(Vec<PairCreatedData>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PairCreatedData {
+    pub nft_origin: AccountId,
+    pub pair_account: AccountId,
+    pub symbol: String,
+}
+
+impl std::fmt::Display for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("EVENT_JSON:")?;
+        f.write_str(
+            &serde_json::to_string(self).map_err(|_| std::fmt::Error)?,
+        )
+    }
+}
+
+impl EventLog {
+    /// Build an event line from a single variant.
+    pub fn new(event: EventLogVariant) -> Self {
+        Self {
+            standard: NFT141_STANDARD,
+            version: NFT141_VERSION,
+            event,
+        }
+    }
+
+    /// Write the event to the transaction logs.
+    pub fn emit(self) {
+        near_sdk::env::log(self.to_string().as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_created_log_string() {
+        let event = EventLog::new(EventLogVariant::PairCreated(vec![PairCreatedData {
+            nft_origin: "nft.testnet".to_string(),
+            pair_account: "yti.factory.testnet".to_string(),
+            symbol: "YTI".to_string(),
+        }]));
+        assert_eq!(
+            event.to_string(),
+            "EVENT_JSON:{\"standard\":\"nft141\",\"version\":\"1.0.0\",\"event\":\"pair_created\",\"data\":[{\"nft_origin\":\"nft.testnet\",\"pair_account\":\"yti.factory.testnet\",\"symbol\":\"YTI\"}]}"
+        );
+    }
+}
@@ -1,15 +1,18 @@
-use std::convert::TryInto;
+use std::collections::HashSet;
 
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
     collections::{LookupMap, Vector},
-    ext_contract, near_bindgen,
+    ext_contract, near_bindgen, PanicOnDefault,
     setup_alloc, log, BorshStorageKey,
     serde::{Deserialize, Serialize},
     env, Promise, AccountId, PromiseResult,
-    json_types::{ValidAccountId, U64, U128},
+    json_types::{U64, U128},
 };
 
+mod events;
+use events::{EventLog, EventLogVariant, PairCreatedData};
+
 setup_alloc!();
 pub const TGAS: u64 = 1_000_000_000_000;
 pub const NO_DEPOSIT: u128 = 0;
@@ -21,7 +24,9 @@ pub trait NFT141Pair {
         nft_contract_address: AccountId,
         vault_name: String,
         vault_symbol: String,
-        feature_media: String
+        feature_media: String,
+        owner_id: AccountId,
+        fee: U128
     );
     fn get_infos(self) -> PairInfos;
     fn setParams(
@@ -29,7 +34,8 @@ pub trait NFT141Pair {
         _name: String,
         _symbol: String,
         _value: U128,
-        _media: String
+        _media: String,
+        _fee: U128
     );
 }
 
@@ -47,38 +53,131 @@ pub struct PairInfos {
     pub media: String
 }
 
+/// Coarse-grained capabilities that can be granted to non-owner accounts so the
+/// owner does not have to sign every routine operation. Roles are additive; the
+/// owner implicitly holds all of them.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// May update the protocol fee via `setFee`.
+    FeeManager,
+    /// May reconfigure deployed pairs via `setValue`.
+    PairAdmin
+}
+
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKeyEnum {
     NftToToken,
     IndexToNft,
-    PairsInfo
+    PairsInfo,
+    Roles
 }
 
 #[near_bindgen]
-#[derive(BorshDeserialize, BorshSerialize)]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct NFT141Factory {
     // keep track of nft address to pair address
     nft_to_token: LookupMap<AccountId, AccountId>,
     index_to_nft: LookupMap<u64, AccountId>,
     pairs_info: Vec<PairInfos>,
     counter: u64,
-    fee: U128 
+    fee: U128,
+    // access control
+    owner_id: AccountId,
+    pending_owner: Option<AccountId>,
+    roles: LookupMap<AccountId, HashSet<Role>>,
+    paused: bool
 }
 
-impl Default for NFT141Factory {
-    fn default() -> Self {
+impl NFT141Factory {
+    fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "!owner");
+    }
+
+    fn assert_role(&self, role: Role) {
+        let caller = env::predecessor_account_id();
+        if caller == self.owner_id {
+            return;
+        }
+        let granted = self.roles.get(&caller).map(|r| r.contains(&role)).unwrap_or(false);
+        assert!(granted, "!authorized");
+    }
+
+    fn assert_not_paused(&self) {
+        assert!(!self.paused, "contract is paused");
+    }
+}
+
+#[near_bindgen]
+impl NFT141Factory {
+    /// Deploy the factory with an explicit human owner. Ownership is never
+    /// defaulted to the contract account, so it can be handed off through
+    /// `propose_owner`/`accept_owner` from a normal account.
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        assert!(!env::state_exists(), "Already initialized");
         Self {
             nft_to_token: LookupMap::<AccountId, AccountId>::new(StorageKeyEnum::NftToToken),
             index_to_nft: LookupMap::<u64, AccountId>::new(StorageKeyEnum::IndexToNft),
             pairs_info: Vec::new(),
             counter: 0,
-            fee: U128::from(0)
+            fee: U128::from(0),
+            owner_id,
+            pending_owner: None,
+            roles: LookupMap::<AccountId, HashSet<Role>>::new(StorageKeyEnum::Roles),
+            paused: false
         }
     }
-}
 
-#[near_bindgen]
-impl NFT141Factory {
+    /// Account that currently owns the factory.
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Whether state-changing methods are currently blocked.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Step one of ownership transfer: the owner nominates a successor. The
+    /// transfer only completes once the successor calls `accept_owner`, so a
+    /// mistyped account can never brick the contract.
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        self.pending_owner = Some(new_owner);
+    }
+
+    /// Step two of ownership transfer: the pending owner accepts the role.
+    pub fn accept_owner(&mut self) {
+        let pending = self.pending_owner.take().expect("no pending owner");
+        assert_eq!(env::predecessor_account_id(), pending, "!pending owner");
+        self.owner_id = pending;
+    }
+
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        roles.insert(role);
+        self.roles.insert(&account_id, &roles);
+    }
+
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        roles.remove(&role);
+        self.roles.insert(&account_id, &roles);
+    }
+
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        self.paused = false;
+    }
+
     #[payable]
     pub fn nft141Pair(
         &mut self,
@@ -88,6 +187,7 @@ impl NFT141Factory {
         feature_media: String
     ) {
         // assert valid nft origin contract address
+        self.assert_not_paused();
 
         assert_eq!(self.nft_to_token.get(&nft_origin), None, "Found this contract address before");
         // Deploy pair contract
@@ -100,14 +200,14 @@ impl NFT141Factory {
             .add_full_access_key(env::signer_account_pk())
             .deploy_contract(include_bytes!("../../nft141pair/res/nft141pair.wasm").to_vec());
 
-        let owner: ValidAccountId = env::signer_account_id().try_into().unwrap();
-
         // Call pair contract constructor
         ext_pair::init_vault(
-            nft_origin.clone(), 
-            name.clone(), 
+            nft_origin.clone(),
+            name.clone(),
             nft_symbol.clone(),
             feature_media.clone(),
+            self.owner_id.clone(),
+            self.fee,
             &pair_contract,
             0,
             env::prepaid_gas() / 3
@@ -115,9 +215,14 @@ impl NFT141Factory {
 
         self.nft_to_token.insert(&nft_origin, &pair_contract);
         self.index_to_nft.insert(&self.counter, &nft_origin);
-        self.counter += 1
-
-        //emit event
+        self.counter += 1;
+
+        EventLog::new(EventLogVariant::PairCreated(vec![PairCreatedData {
+            nft_origin,
+            pair_account: pair_contract,
+            symbol: nft_symbol,
+        }]))
+        .emit();
     }
 
     pub fn getPairByNftAddress(&self, index: u64) {
@@ -167,12 +272,15 @@ impl NFT141Factory {
         _value: U128,
         _media: String
     ) {
-        //assert owner
+        self.assert_not_paused();
+        self.assert_role(Role::PairAdmin);
+        // push the current protocol fee down to the pair alongside its params
         ext_pair::setParams(
             _name,
             _symbol,
             _value,
             _media,
+            self.fee,
             &_pair,
             0,
             env::prepaid_gas() / 2
@@ -180,10 +288,51 @@ impl NFT141Factory {
     }
 
     pub fn setFee(&mut self, _fee: U128) {
-        //assert owner
+        self.assert_not_paused();
+        self.assert_role(Role::FeeManager);
         self.fee = _fee;
     }
 
+    /// Forward a replacement wasm to a deployed pair's `upgrade`, which accepts
+    /// calls from the pair's owner or from this factory.
+    /// Both arguments are borsh-serialized so the method decodes its input under
+    /// a single scheme (near-sdk deserializes the whole input at once, so a JSON
+    /// `pair` mixed with a borsh `code` would not decode). The raw `code` bytes
+    /// become the pair call's input, which the pair reads from its own register.
+    pub fn upgrade_pair(
+        &self,
+        #[serializer(borsh)] pair: AccountId,
+        #[serializer(borsh)] code: Vec<u8>
+    ) -> Promise {
+        self.assert_owner();
+        Promise::new(pair).function_call(
+            b"upgrade".to_vec(),
+            code,
+            0,
+            env::prepaid_gas() / 2
+        )
+    }
+
+    /// Owner-only self-upgrade of the factory. Mirrors `NFT141Pair::upgrade`:
+    /// new code is read from the input register and deployed atomically with a
+    /// `migrate` call.
+    pub fn upgrade(&self) -> Promise {
+        self.assert_owner();
+        let code = env::input().expect("no code in input register");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, env::prepaid_gas() / 2)
+    }
+
+    /// Borsh-reads the previous factory layout and rewrites the current one,
+    /// running the `UpgradeHook` for any bespoke migration logic.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let this: NFT141Factory = env::state_read().expect("failed to read state");
+        this.on_upgrade();
+        this
+    }
+
     pub fn pair_info_callback(&mut self) -> PairInfos {
         assert_eq!(
             env::promise_results_count(),
@@ -203,6 +352,18 @@ impl NFT141Factory {
     }
 }
 
+/// Run as part of a state migration so a freshly deployed factory version can
+/// perform custom fix-up logic before it starts serving calls.
+pub trait UpgradeHook {
+    fn on_upgrade(&self);
+}
+
+impl UpgradeHook for NFT141Factory {
+    fn on_upgrade(&self) {
+        // No bespoke migration logic required for this version.
+    }
+}
+
 fn get_pair_contract_name(_target: String) -> String {
     let prefix = _target.replace(".", "-");
     format!("{}.{}", prefix, env::current_account_id()).to_lowercase()
@@ -250,7 +411,7 @@ mod tests {
         // let target_nft_contract = "nft.testnet".to_string();
         // let nft_token_id = "0".to_string();
 
-        let mut contract = NFT141Factory::default();
+        let mut contract = NFT141Factory::new("jane.testnet".to_string());
 
         contract.nft141Pair(
             "Yeti".into(), 
@@ -272,7 +433,69 @@ mod tests {
     }
 
     #[test]
-    fn pair_contract_grant_escrow_access() {
+    #[should_panic(expected = "!authorized")]
+    fn set_fee_requires_role() {
+        // predecessor (jane.testnet) is neither owner nor a FeeManager
+        let context = get_context(vec![], false);
+        testing_env!(context);
+
+        let mut contract = NFT141Factory::new("alice.testnet".to_string());
+        contract.setFee(U128::from(5));
+    }
+
+    #[test]
+    fn owner_can_upgrade() {
+        // owner redeploys the factory from the input register; the method must
+        // build the deploy+migrate promise rather than panic.
+        let mut context = get_context(vec![0, 1, 2, 3], false);
+        context.predecessor_account_id = "alice.testnet".to_string();
+        testing_env!(context);
+
+        let contract = NFT141Factory::new("alice.testnet".to_string());
+        contract.upgrade();
+    }
+
+    #[test]
+    fn migrate_preserves_state() {
+        // `migrate` must borsh-read the persisted layout back out unchanged so a
+        // redeploy keeps ownership and counters intact.
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = "alice.testnet".to_string();
+        testing_env!(context);
+
+        let contract = NFT141Factory::new("alice.testnet".to_string());
+        near_sdk::env::state_write(&contract);
+
+        let migrated = NFT141Factory::migrate();
+        assert_eq!(migrated.owner_id, contract.owner_id);
+        assert_eq!(migrated.counter, contract.counter);
+    }
+
+    #[test]
+    fn upgrade_pair_forwards_code_to_pair() {
+        // owner forwards a replacement wasm to a deployed pair; the method must
+        // build the forwarding promise rather than panic, exercising the path
+        // the reject-only test cannot reach.
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = "alice.testnet".to_string();
+        testing_env!(context);
+
+        let contract = NFT141Factory::new("alice.testnet".to_string());
+        contract.upgrade_pair("yti.alice.testnet".to_string(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn owner_can_set_fee_and_grant_role() {
+        // make the predecessor the owner so it can configure the factory
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = "alice.testnet".to_string();
+        testing_env!(context);
+
+        let mut contract = NFT141Factory::new("alice.testnet".to_string());
+        contract.setFee(U128::from(7));
+        assert_eq!(contract.fee.0, 7);
 
+        contract.grant_role("bob.testnet".to_string(), Role::FeeManager);
+        assert!(contract.roles.get(&"bob.testnet".to_string()).unwrap().contains(&Role::FeeManager));
     }
 }
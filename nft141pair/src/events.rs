@@ -0,0 +1,129 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{serde_json, AccountId};
+
+use crate::TokenId;
+
+/// NEP-297 `standard` tag carried by every log this pair emits.
+pub const NFT141_STANDARD: &str = "nft141";
+/// Semantic version of the event schema; bump on a breaking data change.
+pub const NFT141_VERSION: &str = "1.0.0";
+
+/// A single NEP-297 compliant event line. Serializes to
+/// `{"standard":"nft141","version":"1.0.0","event":<name>,"data":[...]}` and is
+/// logged behind the mandated `EVENT_JSON:` prefix via `Display`/`emit`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: &'static str,
+    pub version: &'static str,
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+/// The set of machine-readable activities a pair reports. New activities are
+/// added by appending a variant, keeping old indexers forward compatible.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EventLogVariant {
+    NftDeposited(Vec<NftDepositedData>),
+    NftWithdrawn(Vec<NftWithdrawnData>),
+    TokensBurned(Vec<TokensBurnedData>),
+    Swap(Vec<SwapData>),
+    FeeCollected(Vec<FeeCollectedData>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftDepositedData {
+    pub account: AccountId,
+    pub token_ids: Vec<TokenId>,
+    pub minted_amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftWithdrawnData {
+    pub account: AccountId,
+    pub token_ids: Vec<TokenId>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokensBurnedData {
+    pub account: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapData {
+    pub account: AccountId,
+    pub in_id: TokenId,
+    pub out_id: TokenId,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeCollectedData {
+    pub account: AccountId,
+    pub amount: U128,
+}
+
+impl std::fmt::Display for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("EVENT_JSON:")?;
+        f.write_str(
+            &serde_json::to_string(self).map_err(|_| std::fmt::Error)?,
+        )
+    }
+}
+
+impl EventLog {
+    /// Build an event line from a single variant.
+    pub fn new(event: EventLogVariant) -> Self {
+        Self {
+            standard: NFT141_STANDARD,
+            version: NFT141_VERSION,
+            event,
+        }
+    }
+
+    /// Write the event to the transaction logs.
+    pub fn emit(self) {
+        near_sdk::env::log(self.to_string().as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swap_log_string() {
+        let event = EventLog::new(EventLogVariant::Swap(vec![SwapData {
+            account: "alice.testnet".to_string(),
+            in_id: "1".to_string(),
+            out_id: "2".to_string(),
+        }]));
+        assert_eq!(
+            event.to_string(),
+            "EVENT_JSON:{\"standard\":\"nft141\",\"version\":\"1.0.0\",\"event\":\"swap\",\"data\":[{\"account\":\"alice.testnet\",\"in_id\":\"1\",\"out_id\":\"2\"}]}"
+        );
+    }
+
+    #[test]
+    fn nft_deposited_log_string() {
+        let event = EventLog::new(EventLogVariant::NftDeposited(vec![NftDepositedData {
+            account: "alice.testnet".to_string(),
+            token_ids: vec!["1".to_string(), "2".to_string()],
+            minted_amount: U128::from(200),
+        }]));
+        assert_eq!(
+            event.to_string(),
+            "EVENT_JSON:{\"standard\":\"nft141\",\"version\":\"1.0.0\",\"event\":\"nft_deposited\",\"data\":[{\"account\":\"alice.testnet\",\"token_ids\":[\"1\",\"2\"],\"minted_amount\":\"200\"}]}"
+        );
+    }
+}
@@ -1,22 +1,33 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    collections::{LookupMap, LazyOption}, Balance, PromiseOrValue,
+    collections::{LookupMap, LazyOption, Vector}, Balance, PromiseOrValue,
     ext_contract, near_bindgen, PanicOnDefault,
     setup_alloc, log, BorshStorageKey,
     serde::{Deserialize, Serialize},
-    env, Promise, AccountId,
+    env, Gas, Promise, PromiseResult, AccountId,
     json_types::{ValidAccountId, U64, U128},
 };
+mod events;
 mod metadata;
+use events::{
+    EventLog, EventLogVariant, FeeCollectedData, NftDepositedData, NftWithdrawnData, SwapData,
+    TokensBurnedData,
+};
 use metadata::{NFT141PairMetadata, NFT141PairMetadataProvider, NFT141_FT_METADATA_SPEC};
 
 setup_alloc!();
 
 pub type TokenId = String;
 
+/// Gas reserved for a single `nft_transfer` cross-contract call.
+pub const GAS_FOR_NFT_TRANSFER: Gas = 20_000_000_000_000;
+/// Gas reserved for the deposit/withdraw resolution callback.
+pub const GAS_FOR_RESOLVE: Gas = 30_000_000_000_000;
+
 #[ext_contract]
 pub trait NonFungibleTokenCore {
     fn nft_transfer(
@@ -28,10 +39,47 @@ pub trait NonFungibleTokenCore {
     );
 }
 
+#[ext_contract(ext_self)]
+pub trait ResolveTransfer {
+    fn on_deposit_resolved(&mut self, account: AccountId, token_ids: Vec<TokenId>);
+    fn on_withdraw_resolved(&mut self, account: AccountId, token_ids: Vec<TokenId>);
+    fn on_swap_resolved(&mut self, account: AccountId, in_id: TokenId, out_id: TokenId);
+}
+
+/// Coarse-grained capabilities that can be granted to non-owner accounts. The
+/// owner (and the deploying factory) implicitly hold all roles.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    /// May reconfigure this pair via `setParams`.
+    PairAdmin
+}
+
 #[derive(BorshSerialize, BorshStorageKey)]
 enum StorageKeyEnum {
     FungibleToken,
-    Metadata
+    Metadata,
+    Roles,
+    VaultedTokens,
+    History
+}
+
+/// Direction of a single inventory movement.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Direction {
+    Deposit,
+    Withdraw
+}
+
+/// One append-only entry in the pair's flow history.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferRecord {
+    pub account: AccountId,
+    pub token_id: TokenId,
+    pub direction: Direction,
+    pub block_timestamp: U64
 }
 
 #[near_bindgen]
@@ -42,9 +90,22 @@ pub struct NFT141Pair {
     pub factory_contract_address: AccountId,
     pub nft_contract_address: AccountId,
     pub nft_value: U128,
+    // protocol fee (absolute FT amount) charged per NFT on top of `nft_value`
+    pub fee: U128,
+    // fees accrued to the collector awaiting `claim_fees`, tracked apart from
+    // the bootstrap incentive so a claim never sweeps the reserve
+    pub accrued_fees: Balance,
     pub vault_name: String,
     pub vault_symbol: String,
-    pub feature_media: String
+    pub feature_media: String,
+    // access control
+    pub owner_id: AccountId,
+    pub pending_owner: Option<AccountId>,
+    pub roles: LookupMap<AccountId, HashSet<Role>>,
+    pub paused: bool,
+    // inventory + history index
+    pub vaulted: Vector<TokenId>,
+    pub history: Vector<TransferRecord>
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -56,14 +117,131 @@ pub struct PairInfos {
     pub media: String,
 }
 
+impl NFT141Pair {
+    fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "!owner");
+    }
+
+    /// Authorize an in-place upgrade. The human owner may redeploy directly, and
+    /// the deploying factory may forward a redeploy via `upgrade_pair`; both
+    /// arrive with a different `predecessor`, so checking only `owner_id` would
+    /// leave the factory path dead.
+    fn assert_can_upgrade(&self) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == self.owner_id || caller == self.factory_contract_address,
+            "!owner"
+        );
+    }
+
+    fn assert_can_configure(&self) {
+        let caller = env::predecessor_account_id();
+        if caller == self.factory_contract_address || caller == self.owner_id {
+            return;
+        }
+        let granted = self.roles.get(&caller).map(|r| r.contains(&Role::PairAdmin)).unwrap_or(false);
+        assert!(granted, "!authorized");
+    }
+
+    fn assert_not_paused(&self) {
+        assert!(!self.paused, "contract is paused");
+    }
+
+    /// FT account where protocol fees accumulate before being claimed.
+    fn fee_collector(&self) -> AccountId {
+        env::current_account_id()
+    }
+
+    /// Record an NFT entering the vault and append a `Deposit` history entry.
+    fn record_deposit(&mut self, account: &AccountId, token_id: &TokenId) {
+        self.vaulted.push(token_id);
+        self.history.push(&TransferRecord {
+            account: account.clone(),
+            token_id: token_id.clone(),
+            direction: Direction::Deposit,
+            block_timestamp: U64::from(env::block_timestamp())
+        });
+    }
+
+    /// Record an NFT leaving the vault and append a `Withdraw` history entry.
+    /// The id is removed from the inventory via `swap_remove`, so ordering is
+    /// not preserved but lookups stay O(n) on a small vault.
+    fn record_withdraw(&mut self, account: &AccountId, token_id: &TokenId) {
+        let len = self.vaulted.len();
+        let mut i: u64 = 0;
+        while i < len {
+            if &self.vaulted.get(i).unwrap() == token_id {
+                self.vaulted.swap_remove(i);
+                break;
+            }
+            i += 1;
+        }
+        self.history.push(&TransferRecord {
+            account: account.clone(),
+            token_id: token_id.clone(),
+            direction: Direction::Withdraw,
+            block_timestamp: U64::from(env::block_timestamp())
+        });
+    }
+}
+
 #[near_bindgen]
 impl NFT141Pair {
+    /// Account that currently owns this pair.
+    pub fn owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Whether state-changing methods are currently blocked.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Nominate a successor owner; the transfer completes on `accept_owner`.
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        self.pending_owner = Some(new_owner);
+    }
+
+    /// Accept a pending ownership transfer.
+    pub fn accept_owner(&mut self) {
+        let pending = self.pending_owner.take().expect("no pending owner");
+        assert_eq!(env::predecessor_account_id(), pending, "!pending owner");
+        self.owner_id = pending;
+    }
+
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        roles.insert(role);
+        self.roles.insert(&account_id, &roles);
+    }
+
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles = self.roles.get(&account_id).unwrap_or_default();
+        roles.remove(&role);
+        self.roles.insert(&account_id, &roles);
+    }
+
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        self.paused = false;
+    }
+
     #[init]
     pub fn init_vault(
         nft_contract_address: AccountId,
         vault_name: String,
         vault_symbol: String,
-        feature_media: String
+        feature_media: String,
+        owner_id: AccountId,
+        fee: U128
     ) -> Self {
         // assert!(factory == address(0)); //Watch out TEST this is so we can init several time
         assert!(!env::state_exists(), "Already initialized");
@@ -85,11 +263,19 @@ impl NFT141Pair {
             factory_contract_address: env::predecessor_account_id(),
             nft_contract_address,
             nft_value: U128::from(100 * 10u128.pow(24)),
+            fee,
+            accrued_fees: 0,
             vault_name,
             vault_symbol,
-            feature_media
+            feature_media,
+            owner_id,
+            pending_owner: None,
+            roles: LookupMap::<AccountId, HashSet<Role>>::new(StorageKeyEnum::Roles),
+            paused: false,
+            vaulted: Vector::new(StorageKeyEnum::VaultedTokens),
+            history: Vector::new(StorageKeyEnum::History)
         };
-        
+
         // incentive
         this.token.internal_register_account(&env::current_account_id());
         this.token.internal_deposit(&env::current_account_id(), this.nft_value.0);
@@ -98,11 +284,13 @@ impl NFT141Pair {
     }
 
     pub fn get_infos(self) -> PairInfos {
-        // Handle '0' supply value?
         PairInfos {
             name: self.vault_name,
             symbol: self.vault_symbol,
-            supply: U128::from(self.token.total_supply / self.nft_value.0 - 1),
+            // Redeemable supply is the number of NFTs held in the vault. Deriving
+            // it from `total_supply` would over-count, since deposit fees minted
+            // into the pool inflate the balance without backing any NFT.
+            supply: U128::from(self.vaulted.len() as u128),
             media: self.feature_media
         }
     }
@@ -111,31 +299,80 @@ impl NFT141Pair {
         self.nft_contract_address
     }
 
+    /// Paginated view of the NFT ids currently held by the vault.
+    pub fn nft_tokens_in_vault(&self, from_index: u64, limit: u64) -> Vec<TokenId> {
+        (from_index..std::cmp::min(from_index + limit, self.vaulted.len()))
+            .map(|i| self.vaulted.get(i).unwrap())
+            .collect()
+    }
+
+    /// Paginated view of the append-only deposit/withdraw history.
+    pub fn transfer_history(&self, from_index: u64, limit: u64) -> Vec<TransferRecord> {
+        (from_index..std::cmp::min(from_index + limit, self.history.len()))
+            .map(|i| self.history.get(i).unwrap())
+            .collect()
+    }
+
     #[payable]
     pub fn swap171(&mut self, _in: String, _out: String) {
-        // Check approved?
+        self.assert_not_paused();
 
-        // Performing swap
-        non_fungible_token_core::nft_transfer(
+        // Pull the incoming id into the vault and push the outgoing one out. The
+        // callback writes each leg to the inventory only once it has landed, so
+        // a failed transfer cannot desync the vault.
+        let account = env::predecessor_account_id();
+        let pull_in = non_fungible_token_core::nft_transfer(
             env::current_account_id().try_into().unwrap(),
             _in.clone(),
             None,
             None,
             &self.nft_contract_address,
             1,
-            env::prepaid_gas() / 2
+            GAS_FOR_NFT_TRANSFER
         );
-        non_fungible_token_core::nft_transfer(
-            env::signer_account_id().try_into().unwrap(),
+        let push_out = non_fungible_token_core::nft_transfer(
+            account.clone().try_into().unwrap(),
             _out.clone(),
             None,
             None,
             &self.nft_contract_address,
             1,
-            env::prepaid_gas() / 2
+            GAS_FOR_NFT_TRANSFER
         );
 
-        //Emit events
+        pull_in.and(push_out).then(ext_self::on_swap_resolved(
+            account,
+            _in,
+            _out,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE
+        ));
+    }
+
+    /// Commit each transfer leg to the inventory only if it landed. Leg 0 is the
+    /// incoming id, leg 1 the outgoing one; a failed leg leaves its id's
+    /// inventory untouched, and the swap event is emitted only if both landed.
+    #[private]
+    pub fn on_swap_resolved(&mut self, account: AccountId, in_id: TokenId, out_id: TokenId) {
+        let in_ok = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        let out_ok = matches!(env::promise_result(1), PromiseResult::Successful(_));
+        if in_ok {
+            self.record_deposit(&account, &in_id);
+        }
+        if out_ok {
+            self.record_withdraw(&account, &out_id);
+        }
+        if !(in_ok && out_ok) {
+            return;
+        }
+
+        EventLog::new(EventLogVariant::Swap(vec![SwapData {
+            account,
+            in_id,
+            out_id,
+        }]))
+        .emit();
     }
 
     #[payable]
@@ -143,78 +380,216 @@ impl NFT141Pair {
         &mut self,
         _ids: Vec<String>
     ) {
-
-        let mut i: u64 = 0;
-        while i < _ids.len() as u64 {
-            let tokenId: &String = _ids.get(i as usize).unwrap();
+        self.assert_not_paused();
+        assert!(!_ids.is_empty(), "No token ids provided");
+        let account = env::predecessor_account_id();
+
+        // Pull each NFT into the vault. The FT is only minted once the callback
+        // confirms which transfers actually succeeded, so a failed transfer can
+        // never hand the depositor free tokens.
+        let mut transfers = _ids.iter().map(|token_id| {
             non_fungible_token_core::nft_transfer(
-                env::current_account_id().clone().try_into().unwrap(),
-                tokenId.clone(),
+                env::current_account_id().try_into().unwrap(),
+                token_id.clone(),
                 None,
                 None,
                 &self.nft_contract_address,
                 1,
-                env::prepaid_gas() / 2
-            );
+                GAS_FOR_NFT_TRANSFER
+            )
+        });
 
-            i = i + 1;
+        let mut joint = transfers.next().unwrap();
+        for transfer in transfers {
+            joint = joint.and(transfer);
         }
 
-        //Check success logs here
-        //Start mingting NEP-141 token
-        if self.token.accounts.get(&env::predecessor_account_id()) == None {
-            self.token.internal_register_account(&env::predecessor_account_id());
+        joint.then(ext_self::on_deposit_resolved(
+            account,
+            _ids,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE
+        ));
+    }
+
+    /// Mint FT only for the NFTs that actually landed in the vault. Failed legs
+    /// simply mint nothing; `multi_nft_deposits` never escrows a storage bond
+    /// from the caller, so there is nothing to refund.
+    #[private]
+    pub fn on_deposit_resolved(&mut self, account: AccountId, token_ids: Vec<TokenId>) {
+        let mut minted_ids: Vec<TokenId> = Vec::new();
+        for i in 0..env::promise_results_count() {
+            if let PromiseResult::Successful(_) = env::promise_result(i) {
+                minted_ids.push(token_ids[i as usize].clone());
+            }
+        }
+        self.credit_deposit(account, minted_ids);
+    }
+
+    /// Settle a confirmed deposit: mint FT to the depositor, mint the protocol
+    /// cut to the fee collector, update the inventory and emit the events.
+    fn credit_deposit(&mut self, account: AccountId, minted_ids: Vec<TokenId>) {
+        if minted_ids.is_empty() {
+            return;
+        }
+
+        if self.token.accounts.get(&account) == None {
+            self.token.internal_register_account(&account);
+        }
+        let minted_amount = minted_ids.len() as u128 * self.nft_value.0;
+        self.token.internal_deposit(&account, minted_amount);
+
+        // Mint the protocol cut to the fee collector (the pair account itself),
+        // where it accrues until the owner sweeps it via `claim_fees`.
+        let collected = minted_ids.len() as u128 * self.fee.0;
+        if collected > 0 {
+            if self.token.accounts.get(&self.fee_collector()) == None {
+                self.token.internal_register_account(&self.fee_collector());
+            }
+            self.token.internal_deposit(&self.fee_collector(), collected);
+            self.accrued_fees += collected;
+            EventLog::new(EventLogVariant::FeeCollected(vec![FeeCollectedData {
+                account: self.fee_collector(),
+                amount: U128::from(collected),
+            }]))
+            .emit();
+        }
+
+        for token_id in &minted_ids {
+            self.record_deposit(&account, token_id);
         }
-        self.token.internal_deposit(&env::predecessor_account_id(), _ids.len() as u128 * self.nft_value.0);
+
+        EventLog::new(EventLogVariant::NftDeposited(vec![NftDepositedData {
+            account,
+            token_ids: minted_ids,
+            minted_amount: U128::from(minted_amount),
+        }]))
+        .emit();
     }
 
     #[payable]
     pub fn withdraw(&mut self, _id: String) {
-        // Check token balance in wallet
-        let user_account = env::predecessor_account_id();
-        let user_balance = self.ft_balance_of(user_account.clone().try_into().unwrap());
-        assert!(&user_balance.0 >= &self.nft_value.0, "Token balance is smaller than the nft value");
-        // Promise transfer here
-        non_fungible_token_core::nft_transfer(
-            user_account.clone().try_into().unwrap(),
-            _id.clone(),
-            None,
-            None,
-            &self.nft_contract_address,
-            1,
-            env::prepaid_gas() / 3
-        );
-        // Burn nep141 in wallet
-        self.token.accounts.insert(&user_account, &(user_balance.0 - self.nft_value.0));
-        self.token.total_supply -= &self.nft_value.0;
-        self.on_tokens_burned(user_account.clone(), self.nft_value.0);
+        self.assert_not_paused();
+        self.internal_withdraw(vec![_id]);
     }
 
     #[payable]
     pub fn batch_withdraw(&mut self, _ids: Vec<String>) {
-        let user_balance = self.ft_balance_of(env::predecessor_account_id().try_into().unwrap());
-        assert!(user_balance.0 >= self.nft_value.0 * _ids.len() as u128,  "Token balance is smaller than the nft batch value");
+        self.assert_not_paused();
+        self.internal_withdraw(_ids);
+    }
 
-        let mut i: usize = 0;
-        while i < _ids.len() {
-            let tokenId: &String = _ids.get(i as usize).unwrap();
+    /// Send the NFTs out first and settle the burn only in the callback. The FT
+    /// is reserved (debited) up front so the same balance cannot be spent twice
+    /// while transfers are in flight; failed legs are re-credited and only the
+    /// confirmed legs reduce `total_supply`.
+    fn internal_withdraw(&mut self, _ids: Vec<String>) {
+        assert!(!_ids.is_empty(), "No token ids provided");
+        let user_account = env::predecessor_account_id();
+        let user_balance = self.ft_balance_of(user_account.clone().try_into().unwrap());
+        // The user burns `nft_value` plus the protocol `fee` for each NFT.
+        let reserved = (self.nft_value.0 + self.fee.0) * _ids.len() as u128;
+        assert!(user_balance.0 >= reserved, "Token balance is smaller than the nft value");
+
+        // Reserve the FT so it cannot be double-spent while transfers are pending.
+        self.token.accounts.insert(&user_account, &(user_balance.0 - reserved));
+
+        let mut transfers = _ids.iter().map(|token_id| {
             non_fungible_token_core::nft_transfer(
-                env::predecessor_account_id().try_into().unwrap(),
-                tokenId.clone(),
+                user_account.clone().try_into().unwrap(),
+                token_id.clone(),
                 None,
                 None,
                 &self.nft_contract_address,
                 1,
-                env::prepaid_gas() / 2
-            );
+                GAS_FOR_NFT_TRANSFER
+            )
+        });
 
-            i = i + 1;
+        let mut joint = transfers.next().unwrap();
+        for transfer in transfers {
+            joint = joint.and(transfer);
         }
 
-        // Burn nep141 in wallet
-        self.token.accounts.insert(&env::predecessor_account_id(), &(user_balance.0 - self.nft_value.0 * _ids.len() as u128));
-        self.token.total_supply -= self.nft_value.0 * _ids.len() as u128;
-        self.on_tokens_burned(env::predecessor_account_id(), self.nft_value.0 * _ids.len() as u128);
+        joint.then(ext_self::on_withdraw_resolved(
+            user_account,
+            _ids,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE
+        ));
+    }
+
+    /// Finalize a withdrawal: burn the reserved FT for each NFT that left the
+    /// vault and re-credit the user for any transfer that failed.
+    #[private]
+    pub fn on_withdraw_resolved(&mut self, account: AccountId, token_ids: Vec<TokenId>) {
+        let mut withdrawn_ids: Vec<TokenId> = Vec::new();
+        let mut failed: u128 = 0;
+        for i in 0..env::promise_results_count() {
+            match env::promise_result(i) {
+                PromiseResult::Successful(_) => withdrawn_ids.push(token_ids[i as usize].clone()),
+                _ => failed += 1,
+            }
+        }
+        self.settle_withdraw(account, withdrawn_ids, failed);
+    }
+
+    /// Settle a resolved withdrawal against the reserve debited up front. The
+    /// reserve is `(nft_value + fee)` per id. Confirmed legs burn `nft_value`
+    /// (dropping `total_supply`) and move `fee` to the collector as a pure
+    /// balance credit — minting there would double-count the fee and break the
+    /// `total_supply == Σ balances` invariant. Failed legs return their whole
+    /// reserve to the user.
+    fn settle_withdraw(&mut self, account: AccountId, withdrawn_ids: Vec<TokenId>, failed: u128) {
+        if failed > 0 {
+            let refunded = failed * (self.nft_value.0 + self.fee.0);
+            let balance = self.token.accounts.get(&account).unwrap_or(0);
+            self.token.accounts.insert(&account, &(balance + refunded));
+        }
+
+        if withdrawn_ids.is_empty() {
+            return;
+        }
+
+        let count = withdrawn_ids.len() as u128;
+        let burned = count * self.nft_value.0;
+        self.token.total_supply -= burned;
+        self.on_tokens_burned(account.clone(), burned);
+
+        // Route the fee portion of the reserve to the collector without touching
+        // `total_supply`: the user's balance was already debited the fee up front.
+        let collected = count * self.fee.0;
+        if collected > 0 {
+            let collector = self.fee_collector();
+            if self.token.accounts.get(&collector) == None {
+                self.token.internal_register_account(&collector);
+            }
+            let collector_balance = self.token.accounts.get(&collector).unwrap_or(0);
+            self.token.accounts.insert(&collector, &(collector_balance + collected));
+            self.accrued_fees += collected;
+            EventLog::new(EventLogVariant::FeeCollected(vec![FeeCollectedData {
+                account: collector,
+                amount: U128::from(collected),
+            }]))
+            .emit();
+        }
+
+        for token_id in &withdrawn_ids {
+            self.record_withdraw(&account, token_id);
+        }
+
+        EventLog::new(EventLogVariant::NftWithdrawn(vec![NftWithdrawnData {
+            account: account.clone(),
+            token_ids: withdrawn_ids,
+        }]))
+        .emit();
+        EventLog::new(EventLogVariant::TokensBurned(vec![TokensBurnedData {
+            account,
+            amount: U128::from(burned),
+        }]))
+        .emit();
     }
 
     pub fn setParams(
@@ -222,13 +597,38 @@ impl NFT141Pair {
         _name: String,
         _symbol: String,
         _value: U128,
-        _media: String
+        _media: String,
+        _fee: U128
     ) {
-        assert_eq!(env::predecessor_account_id(), self.factory_contract_address, "!authorized");
+        self.assert_can_configure();
         self.vault_name = _name;
         self.vault_symbol = _symbol;
         self.nft_value = _value;
         self.feature_media = _media;
+        self.fee = _fee;
+    }
+
+    /// Sweep the accumulated protocol fees from the collector account to the
+    /// owner. Owner-only; registers the owner's FT account on first claim.
+    pub fn claim_fees(&mut self) {
+        self.assert_owner();
+        let collector = self.fee_collector();
+        // Sweep only the fees accrued since the last claim, never the bootstrap
+        // incentive that also lives on the collector account.
+        let amount = self.accrued_fees;
+        assert!(amount > 0, "No fees to claim");
+
+        if self.token.accounts.get(&self.owner_id) == None {
+            self.token.internal_register_account(&self.owner_id);
+        }
+        self.token.internal_transfer(&collector, &self.owner_id, amount, None);
+        self.accrued_fees = 0;
+
+        EventLog::new(EventLogVariant::FeeCollected(vec![FeeCollectedData {
+            account: self.owner_id.clone(),
+            amount: U128::from(amount),
+        }]))
+        .emit();
     }
 
     fn on_account_closed(&mut self, account_id: AccountId, balance: Balance) {
@@ -240,6 +640,42 @@ impl NFT141Pair {
     }
 }
 
+/// Run as part of a state migration so a freshly deployed code version can
+/// perform custom fix-up logic before it starts serving calls.
+pub trait UpgradeHook {
+    fn on_upgrade(&self);
+}
+
+#[near_bindgen]
+impl NFT141Pair {
+    /// In-place code upgrade, callable by the owner or the deploying factory. The replacement wasm is read from the
+    /// input register instead of a method argument to avoid base64 bloat, then
+    /// deployed and followed by `migrate` inside a single promise so the new
+    /// code and the ported state land in the same receipt.
+    pub fn upgrade(&self) -> Promise {
+        self.assert_can_upgrade();
+        let code = env::input().expect("no code in input register");
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(b"migrate".to_vec(), vec![], 0, env::prepaid_gas() / 2)
+    }
+
+    /// Borsh-reads the previous struct layout and rewrites it under the current
+    /// one, giving the `UpgradeHook` a chance to run bespoke migration logic.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let this: NFT141Pair = env::state_read().expect("failed to read state");
+        this.on_upgrade();
+        this
+    }
+}
+
+impl UpgradeHook for NFT141Pair {
+    fn on_upgrade(&self) {
+        // No bespoke migration logic required for this version.
+    }
+}
+
 near_contract_standards::impl_fungible_token_core!(NFT141Pair, token, on_tokens_burned);
 near_contract_standards::impl_fungible_token_storage!(NFT141Pair, token, on_account_closed);
 
@@ -281,11 +717,134 @@ mod tests {
             NFT_CONTRACT_ADDRESS.into(),
             "yeti".into(),
             NFT_SYMBOL.into(),
-            NFT_MEDIA_URI.into()
+            NFT_MEDIA_URI.into(),
+            accounts(1).to_string(),
+            U128::from(0)
         );
         testing_env!(context.is_view(true).build());
 
         assert_eq!(contract.ft_total_supply().0, TOTAL_SUPPLY);
         assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY);
     }
+
+    #[test]
+    #[should_panic(expected = "!owner")]
+    fn upgrade_rejects_non_owner() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let contract = NFT141Pair::init_vault(
+            NFT_CONTRACT_ADDRESS.into(),
+            "yeti".into(),
+            NFT_SYMBOL.into(),
+            NFT_MEDIA_URI.into(),
+            accounts(1).to_string(),
+            U128::from(0)
+        );
+
+        // a different predecessor must not be able to redeploy the pair
+        testing_env!(get_context(accounts(2)).build());
+        contract.upgrade();
+    }
+
+    #[test]
+    fn migrate_preserves_state() {
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let contract = NFT141Pair::init_vault(
+            NFT_CONTRACT_ADDRESS.into(),
+            "yeti".into(),
+            NFT_SYMBOL.into(),
+            NFT_MEDIA_URI.into(),
+            accounts(1).to_string(),
+            U128::from(0)
+        );
+        near_sdk::env::state_write(&contract);
+
+        // `migrate` borsh-reads the old layout and rewrites it; owner and total
+        // supply must survive the redeploy untouched.
+        let migrated = NFT141Pair::migrate();
+        assert_eq!(migrated.owner_id, contract.owner_id);
+        assert_eq!(migrated.token.total_supply, contract.token.total_supply);
+    }
+
+    #[test]
+    fn claim_fees_conserves_total_supply() {
+        let fee: Balance = 3 * 10u128.pow(24);
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = NFT141Pair::init_vault(
+            NFT_CONTRACT_ADDRESS.into(),
+            "yeti".into(),
+            NFT_SYMBOL.into(),
+            NFT_MEDIA_URI.into(),
+            accounts(1).to_string(),
+            U128::from(fee)
+        );
+
+        // Simulate fees accruing to the collector (the pair account). The
+        // bootstrap incentive minted at init also lives here and must be left
+        // untouched by a claim.
+        contract.token.internal_deposit(&accounts(0).to_string(), fee * 5);
+        contract.accrued_fees = fee * 5;
+        let supply_before = contract.ft_total_supply().0;
+
+        // Owner sweeps only the accrued fees; this is an internal transfer, so
+        // the total supply must not move — only balances shift.
+        contract.claim_fees();
+
+        assert_eq!(contract.ft_total_supply().0, supply_before);
+        assert_eq!(contract.accrued_fees, 0);
+        // The collector keeps the bootstrap incentive; only the fees left.
+        assert_eq!(contract.ft_balance_of(accounts(0)).0, TOTAL_SUPPLY);
+        assert_eq!(contract.ft_balance_of(accounts(1)).0, fee * 5);
+    }
+
+    #[test]
+    fn deposit_withdraw_roundtrip_conserves_supply_with_fee() {
+        let nv: Balance = 100 * 10u128.pow(24);
+        let fee: Balance = 3 * 10u128.pow(24);
+        let context = get_context(accounts(1));
+        testing_env!(context.build());
+
+        let mut contract = NFT141Pair::init_vault(
+            NFT_CONTRACT_ADDRESS.into(),
+            "yeti".into(),
+            NFT_SYMBOL.into(),
+            NFT_MEDIA_URI.into(),
+            accounts(1).to_string(),
+            U128::from(fee)
+        );
+
+        let user = accounts(2).to_string();
+        let ids: Vec<TokenId> = vec!["1".into(), "2".into(), "3".into()];
+        let count = ids.len() as u128;
+
+        // collector is the pair account (accounts(0)); user is accounts(2).
+        fn sigma(c: &NFT141Pair) -> Balance {
+            c.ft_balance_of(accounts(0)).0 + c.ft_balance_of(accounts(2)).0
+        }
+
+        // Confirmed deposit of `count` NFTs: mints value to the user and the
+        // fee cut to the collector.
+        contract.credit_deposit(user.clone(), ids.clone());
+        assert_eq!(contract.ft_total_supply().0, sigma(&contract));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, count * nv);
+
+        // The user must burn `nft_value + fee` per NFT on withdraw; fund the fee
+        // portion, then reserve the full amount exactly as `internal_withdraw`.
+        contract.token.internal_deposit(&user, count * fee);
+        let reserved = count * (nv + fee);
+        let bal = contract.token.accounts.get(&user).unwrap();
+        contract.token.accounts.insert(&user, &(bal - reserved));
+
+        // Settle a fully-successful withdrawal.
+        contract.settle_withdraw(user, ids, 0);
+
+        // With a nonzero fee the invariant must still hold after the round trip.
+        assert_eq!(contract.ft_total_supply().0, sigma(&contract));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 0);
+    }
 }